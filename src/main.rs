@@ -24,10 +24,15 @@ async fn main() -> anyhow::Result<()> {
         ),
     };
 
-    // output dir
-    fs::remove_dir_all(dist).await.or_else(ignore_not_found)?;
-    fs::create_dir(dist).await?;
-    fs::create_dir(format!("{dist}/posts")).await?;
+    // output dir (kept between runs so incremental builds can reuse rendered pages)
+    fs::create_dir_all(dist).await?;
+    fs::create_dir_all(format!("{dist}/posts")).await?;
+
+    // load the incremental-build cache of discussion number -> updatedAt. This
+    // cache short-circuits the per-post render only: the index, feeds, search
+    // index and sitemap all enumerate every post, so each build still fetches
+    // the full discussion set from the API.
+    let cache = load_cache().await?;
 
     // copy the assets
     for entry in WalkDir::new(asset) {
@@ -40,7 +45,7 @@ async fn main() -> anyhow::Result<()> {
         let dest = format!("{dist}/{}", path.strip_prefix(asset)?.display());
 
         if path.is_dir() {
-            fs::create_dir(dest).await?;
+            fs::create_dir_all(dest).await?;
         } else {
             fs::copy(path, dest).await?;
         }
@@ -52,6 +57,7 @@ async fn main() -> anyhow::Result<()> {
         .into_iter()
         .filter(|p| p.status == PostStatus::Published)
         .collect();
+    let post_refs: Vec<&Post> = posts.iter().collect();
 
     // create index page
     create_html(
@@ -70,15 +76,20 @@ async fn main() -> anyhow::Result<()> {
     create_html(
         "output/posts.html",
         PostsTemplate {
-            posts: &posts,
+            posts: &post_refs,
             owner: &owner,
         },
     )
     .await?;
 
-    // create post page
+    // create post page (skipping the render of posts unchanged since the last
+    // build; the discussion itself was still fetched above)
     for post in &posts {
         let path = format!("output/posts/{}.html", post.slug);
+        let unchanged = cache.get(&post.number) == Some(&post.updated_at);
+        if unchanged && fs::try_exists(&path).await? {
+            continue;
+        }
         create_html(
             path,
             PostTemplate {
@@ -90,12 +101,225 @@ async fn main() -> anyhow::Result<()> {
         .await?;
     }
 
+    // prune post pages for discussions that are no longer published
+    let keep_posts: std::collections::HashSet<String> =
+        posts.iter().map(|p| format!("{}.html", p.slug)).collect();
+    prune_dir("output/posts", &keep_posts).await?;
+
+    let base_url =
+        std::env::var("SITE_URL").expect("SITE_URL environment variable is required");
+
     // create rss feed
-    create_html("output/rss.xml", RssTemplate { posts: &posts }).await?;
+    create_html(
+        "output/rss.xml",
+        RssTemplate {
+            posts: &post_refs,
+            base_url: &base_url,
+        },
+    )
+    .await?;
+
+    // create json feed
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: owner.name.clone(),
+        home_page_url: base_url.clone(),
+        feed_url: format!("{base_url}/feed.json"),
+        items: posts.iter().map(|p| JsonFeedItem::from_post(p, &base_url)).collect(),
+    };
+    fs::write("output/feed.json", serde_json::to_vec_pretty(&feed)?).await?;
+
+    // create client-side search index
+    let index: Vec<SearchRecord> = posts
+        .iter()
+        .map(|p| SearchRecord {
+            title: p.title.clone(),
+            description: p.description.clone(),
+            slug: p.slug.clone(),
+            labels: p.labels.iter().map(|l| l.name.clone()).collect(),
+            published_at: p.published_at.clone(),
+            body: strip_html(&p.body),
+        })
+        .collect();
+    fs::write("output/search-index.json", serde_json::to_vec(&index)?).await?;
+
+    // create per-label archive pages and feeds
+    fs::create_dir_all(format!("{dist}/labels")).await?;
+
+    let mut labels: std::collections::BTreeMap<String, Vec<&Post>> =
+        std::collections::BTreeMap::new();
+    for post in &posts {
+        for label in &post.labels {
+            labels.entry(label.name.clone()).or_default().push(post);
+        }
+    }
+
+    for (name, label_posts) in &labels {
+        let slug = slugify(name);
+        create_html(
+            format!("output/labels/{slug}.html"),
+            LabelTemplate {
+                name,
+                slug: &slug,
+                posts: label_posts,
+                owner: &owner,
+            },
+        )
+        .await?;
+        create_html(
+            format!("output/labels/{slug}.xml"),
+            RssTemplate {
+                posts: label_posts,
+                base_url: &base_url,
+            },
+        )
+        .await?;
+    }
+
+    // prune label pages/feeds for labels that no longer have published posts
+    let keep_labels: std::collections::HashSet<String> = labels
+        .keys()
+        .flat_map(|name| {
+            let slug = slugify(name);
+            [format!("{slug}.html"), format!("{slug}.xml")]
+        })
+        .collect();
+    prune_dir("output/labels", &keep_labels).await?;
+
+    // create sitemap
+    let mut sitemap = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    let mut push_url = |sitemap: &mut String, loc: String, lastmod: Option<&str>| {
+        sitemap.push_str("<url><loc>");
+        sitemap.push_str(&xml_escape(&loc));
+        sitemap.push_str("</loc>");
+        if let Some(lastmod) = lastmod {
+            sitemap.push_str("<lastmod>");
+            sitemap.push_str(&xml_escape(lastmod));
+            sitemap.push_str("</lastmod>");
+        }
+        sitemap.push_str("</url>\n");
+    };
+    push_url(&mut sitemap, format!("{base_url}/index.html"), None);
+    push_url(&mut sitemap, format!("{base_url}/about.html"), None);
+    push_url(&mut sitemap, format!("{base_url}/posts.html"), None);
+    for post in &posts {
+        push_url(
+            &mut sitemap,
+            format!("{base_url}/posts/{}.html", post.slug),
+            Some(&post.updated_at),
+        );
+    }
+    for name in labels.keys() {
+        push_url(
+            &mut sitemap,
+            format!("{base_url}/labels/{}.html", slugify(name)),
+            None,
+        );
+    }
+    sitemap.push_str("</urlset>\n");
+    fs::write("output/sitemap.xml", sitemap).await?;
+
+    // persist the cache for the next incremental build
+    let next_cache: std::collections::HashMap<i32, String> = posts
+        .iter()
+        .map(|p| (p.number, p.updated_at.clone()))
+        .collect();
+    save_cache(&next_cache).await?;
 
     Ok(())
 }
 
+/// Remove files directly inside `dir` whose names are not in `keep`, so pages
+/// for unpublished, deleted, or renamed posts don't linger across incremental
+/// builds.
+async fn prune_dir(
+    dir: impl AsRef<std::path::Path>,
+    keep: &std::collections::HashSet<String>,
+) -> anyhow::Result<()> {
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        if let Some(name) = name.to_str() {
+            if !keep.contains(name) {
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Load the incremental-build cache mapping discussion `number` to its last
+/// seen `updatedAt`. Returns an empty map when no cache exists yet.
+async fn load_cache() -> anyhow::Result<std::collections::HashMap<i32, String>> {
+    match fs::read(".cache/discussions.json").await {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Persist the incremental-build cache of discussion `number` -> `updatedAt`.
+async fn save_cache(cache: &std::collections::HashMap<i32, String>) -> anyhow::Result<()> {
+    fs::create_dir_all(".cache").await?;
+    fs::write(".cache/discussions.json", serde_json::to_vec_pretty(cache)?).await?;
+    Ok(())
+}
+
+/// Strip HTML tags from rendered `bodyHTML`, leaving a plain-text rendition
+/// suitable for a client-side search index. Consecutive whitespace is
+/// collapsed so the output stays compact.
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Wrap rendered HTML in a CDATA section so raw markup (and stray `&`/`<`)
+/// survives intact in an XML document. Any literal `]]>` is split into
+/// `]]]]><![CDATA[>` so it can't terminate the section early.
+fn cdata(html: &str) -> String {
+    format!("<![CDATA[{}]]>", html.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+/// Escape the five XML predefined entities so interpolated values stay
+/// well-formed inside generated XML.
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Slugify a label name for use in a filename: lowercase, with whitespace and
+/// `/` folded to `_`.
+fn slugify(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' => '_',
+            c if c.is_whitespace() => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
 async fn fetch_posts(owner: &str, repo: &str) -> anyhow::Result<Vec<Post>> {
     let token =
         std::env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN environment variable is required");
@@ -104,7 +328,6 @@ async fn fetch_posts(owner: &str, repo: &str) -> anyhow::Result<Vec<Post>> {
 
     let mut articles = Vec::new();
     let mut cursor: Option<String> = None;
-    let mut has_next_page: bool = false;
 
     // fetch all discussions
     loop {
@@ -113,20 +336,18 @@ async fn fetch_posts(owner: &str, repo: &str) -> anyhow::Result<Vec<Post>> {
             .await
             .expect("Failed to fetch discussions");
 
+        let page_info = &discussions["data"]["repository"]["discussions"]["pageInfo"];
+        let has_next_page = page_info["hasNextPage"].as_bool().unwrap();
+        cursor = page_info["endCursor"].as_str().map(|s| s.to_string());
+
         for discussion in discussions["data"]["repository"]["discussions"]["edges"]
             .as_array()
             .expect("Not expected format. API changed?")
         {
-            println!("{:#?}\n", discussion);
-            cursor = Some(discussion["cursor"].as_str().unwrap().to_string());
             let node = discussion["node"].as_object().unwrap();
             let author = node["author"].as_object().unwrap();
             let category = node["category"].as_object().unwrap();
             let labels = node["labels"]["edges"].as_array().unwrap();
-            has_next_page = discussions["data"]["repository"]["discussions"]["pageInfo"]
-                ["hasNextPage"]
-                .as_bool()
-                .unwrap();
 
             let mut labels_vec = Vec::new();
             for label in labels {
@@ -175,6 +396,16 @@ async fn fetch_posts(owner: &str, repo: &str) -> anyhow::Result<Vec<Post>> {
                     .to_string(),
                 labels: labels_vec,
                 number: node["number"].as_i64().unwrap() as i32,
+                discussion_url: format!(
+                    "https://github.com/{owner}/{repo}/discussions/{}",
+                    node["number"].as_i64().unwrap()
+                ),
+                comments: node["comments"]["nodes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(parse_comment)
+                    .collect(),
             });
         }
         if !has_next_page {
@@ -185,6 +416,31 @@ async fn fetch_posts(owner: &str, repo: &str) -> anyhow::Result<Vec<Post>> {
     Ok(articles)
 }
 
+/// Parse a discussion comment node (and its nested replies, if present) into a
+/// [`Comment`].
+fn parse_comment(node: &serde_json::Value) -> Comment {
+    let author = node["author"].as_object().unwrap();
+    let replies = node["replies"]["nodes"]
+        .as_array()
+        .map(|nodes| nodes.iter().map(parse_comment).collect())
+        .unwrap_or_default();
+    Comment {
+        body: node["bodyHTML"].as_str().unwrap().to_string(),
+        author: Author {
+            name: author["login"].as_str().unwrap().to_string(),
+            avatar: author["avatarUrl"].as_str().unwrap().to_string() + "&s=100",
+        },
+        created_at: node["createdAt"]
+            .as_str()
+            .unwrap()
+            .split('T')
+            .next()
+            .unwrap()
+            .to_string(),
+        replies,
+    }
+}
+
 fn generate_query(owner: &str, repo: &str, cursor: Option<&str>) -> String {
     let mut after = String::new();
     if let Some(cursor) = cursor {
@@ -194,9 +450,8 @@ fn generate_query(owner: &str, repo: &str, cursor: Option<&str>) -> String {
     let query = format!(
         r#"{{
             repository(owner: "{owner}", name: "{repo}") {{
-                discussions(first: 1, {after} orderBy: {{ field: CREATED_AT, direction: DESC }} ) {{
+                discussions(first: 100, {after} orderBy: {{ field: CREATED_AT, direction: DESC }} ) {{
                     edges {{
-                        cursor
                         node {{
                             title
                             createdAt
@@ -217,10 +472,31 @@ fn generate_query(owner: &str, repo: &str, cursor: Option<&str>) -> String {
                                 }}
                             }}
                             number
+                            comments(first: 100) {{
+                                nodes {{
+                                    bodyHTML
+                                    createdAt
+                                    author {{
+                                        login
+                                        avatarUrl
+                                    }}
+                                    replies(first: 100) {{
+                                        nodes {{
+                                            bodyHTML
+                                            createdAt
+                                            author {{
+                                                login
+                                                avatarUrl
+                                            }}
+                                        }}
+                                    }}
+                                }}
+                            }}
                         }}
                     }}
                     pageInfo {{
                         hasNextPage
+                        endCursor
                     }}
                 }}
             }}
@@ -246,7 +522,7 @@ struct AboutTemplate<'a> {
 #[derive(Template)]
 #[template(path = "posts.html", escape = "none", whitespace = "suppress")]
 struct PostsTemplate<'a> {
-    posts: &'a Vec<Post>,
+    posts: &'a [&'a Post],
     owner: &'a Author,
 }
 
@@ -259,9 +535,75 @@ struct PostTemplate<'a> {
 }
 
 #[derive(Template)]
-#[template(path = "rss.xml", whitespace = "suppress")]
+#[template(path = "rss.xml", escape = "none", whitespace = "suppress")]
 struct RssTemplate<'a> {
-    posts: &'a Vec<Post>,
+    posts: &'a [&'a Post],
+    base_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "label.html", escape = "none", whitespace = "suppress")]
+struct LabelTemplate<'a> {
+    name: &'a str,
+    slug: &'a str,
+    posts: &'a [&'a Post],
+    owner: &'a Author,
+}
+
+#[derive(serde::Serialize)]
+struct SearchRecord {
+    title: String,
+    description: String,
+    slug: String,
+    labels: Vec<String>,
+    published_at: String,
+    body: String,
+}
+
+#[derive(serde::Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    summary: String,
+    content_html: String,
+    date_published: String,
+    date_modified: String,
+    authors: Vec<JsonFeedAuthor>,
+    tags: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+impl JsonFeedItem {
+    fn from_post(post: &Post, base_url: &str) -> Self {
+        let url = format!("{base_url}/posts/{}.html", post.slug);
+        JsonFeedItem {
+            id: url.clone(),
+            url,
+            title: post.title.clone(),
+            summary: post.description.clone(),
+            content_html: post.body.clone(),
+            date_published: format!("{}T00:00:00Z", post.published_at),
+            date_modified: format!("{}T00:00:00Z", post.updated_at),
+            authors: vec![JsonFeedAuthor {
+                name: post.author.name.clone(),
+            }],
+            tags: post.labels.iter().map(|l| l.name.clone()).collect(),
+        }
+    }
 }
 
 struct Post {
@@ -277,6 +619,39 @@ struct Post {
     updated_at: String,
     labels: Vec<Label>,
     number: i32,
+    /// URL of the backing GitHub discussion, for the "reply" link.
+    discussion_url: String,
+    comments: Vec<Comment>,
+}
+
+struct Comment {
+    author: Author,
+    body: String,
+    /// yyyy-mm-dd
+    created_at: String,
+    replies: Vec<Comment>,
+}
+
+impl Post {
+    /// The body HTML wrapped in a CDATA section for RSS `<content:encoded>`.
+    fn body_cdata(&self) -> String {
+        cdata(&self.body)
+    }
+
+    /// The title with XML entities escaped for RSS plain-text fields.
+    fn title_escaped(&self) -> String {
+        xml_escape(&self.title)
+    }
+
+    /// The description with XML entities escaped for RSS plain-text fields.
+    fn description_escaped(&self) -> String {
+        xml_escape(&self.description)
+    }
+
+    /// The slug with XML entities escaped, for use in RSS `<link>`/`<guid>`.
+    fn slug_escaped(&self) -> String {
+        xml_escape(&self.slug)
+    }
 }
 
 #[derive(PartialEq)]
@@ -298,11 +673,10 @@ struct Label {
     color: String,
 }
 
-fn ignore_not_found(e: io::Error) -> io::Result<()> {
-    if e.kind() == io::ErrorKind::NotFound {
-        Ok(())
-    } else {
-        Err(e)
+impl Label {
+    /// The slugified label name, matching the per-label page filename.
+    fn slug(&self) -> String {
+        slugify(&self.name)
     }
 }
 